@@ -1,11 +1,47 @@
+use std::cmp::max;
 use std::fmt::{Display, Formatter, Error};
 use std::iter::FromIterator;
 use std::slice::Iter;
 
 use repeat::is_repeating_with_predicate;
 
+#[cfg(feature = "serde")]
+use std::collections::HashMap;
+#[cfg(feature = "serde")]
+use std::sync::Mutex;
+#[cfg(feature = "serde")]
+use serde::{Serialize, Deserialize, Serializer, Deserializer};
+#[cfg(feature = "serde")]
+use serde::de::Error as DeError;
+
+#[cfg(feature = "serde")]
+lazy_static! {
+    static ref CUSTOM_REGISTRY: Mutex<HashMap<String, fn(i32, i32) -> bool>> =
+        Mutex::new(HashMap::new());
+}
+
+/// A process-global registry mapping a `CustomPatternElem`'s `repr` to the function pointer
+/// it was built with, used to rebind `Custom` elements after deserializing them.
+///
+/// Since a `fn(i32, i32) -> bool` can't be serialized directly, a `Custom` element is
+/// serialized as only its `repr`; deserializing one looks the `repr` up here to recover its
+/// `check` function, so any custom elements a pattern might contain must be `register`ed
+/// before that pattern is deserialized.
+#[cfg(feature = "serde")]
+pub mod custom_registry {
+    use super::CUSTOM_REGISTRY;
+
+    /// Registers `check` under `repr`, so that a `CustomPatternElem` with this `repr` can be
+    /// reconstructed when deserialized. Registering the same `repr` twice overwrites the
+    /// previous function.
+    pub fn register(repr: &str, check: fn(i32, i32) -> bool) {
+        CUSTOM_REGISTRY.lock().unwrap().insert(String::from(repr), check);
+    }
+}
+
 /// Operations from one integer to another.
 #[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub enum PatternElem {
     // Listed alphabetically to make equality sorting intuitive.
     Const(i32),
@@ -13,10 +49,22 @@ pub enum PatternElem {
     CubeRoot,
     Custom(CustomPatternElem),
     Div(i32),
+    Gcd(i32),
+    Lcm(i32),
     Meta(Pattern),
     Mod(i32),
     Mult(i32),
+    /// The smallest prime strictly greater than the preceding term; see `check_step`.
+    NextPrime,
     Plus(i32),
+    /// The largest prime strictly less than the preceding term; see `check_step`.
+    PrevPrime,
+    /// A constant-coefficient linear recurrence: `a(n) = sum(coeffs[i] * a(n-i-1)) + constant`.
+    /// Unlike every other variant, checking one of these requires looking back `order` terms
+    /// rather than just the immediately preceding one; see `check_history`.
+    Recurrence { order: usize, coeffs: Vec<i32>, constant: i32 },
+    Shl(u32),
+    Shr(u32),
     Square,
     SquareRoot,
 }
@@ -25,7 +73,9 @@ impl PatternElem {
     pub fn get_operand(&self) -> Option<i32> {
         match *self {
             PatternElem::Plus(i) | PatternElem::Mult(i) |
-            PatternElem::Div(i) | PatternElem::Mod(i) => Some(i),
+            PatternElem::Div(i) | PatternElem::Mod(i) |
+            PatternElem::Gcd(i) | PatternElem::Lcm(i) => Some(i),
+            PatternElem::Shl(i) | PatternElem::Shr(i) => Some(i as i32),
             _ => None
         }
     }
@@ -37,14 +87,376 @@ impl PatternElem {
             (&PatternElem::Cube, &PatternElem::Cube) |
             (&PatternElem::CubeRoot, &PatternElem::CubeRoot) |
             (&PatternElem::Div(_), &PatternElem::Div(_)) |
+            (&PatternElem::Gcd(_), &PatternElem::Gcd(_)) |
+            (&PatternElem::Lcm(_), &PatternElem::Lcm(_)) |
             (&PatternElem::Mod(_), &PatternElem::Mod(_)) |
             (&PatternElem::Mult(_), &PatternElem::Mult(_)) |
+            (&PatternElem::NextPrime, &PatternElem::NextPrime) |
             (&PatternElem::Plus(_), &PatternElem::Plus(_)) |
+            (&PatternElem::PrevPrime, &PatternElem::PrevPrime) |
+            (&PatternElem::Shl(_), &PatternElem::Shl(_)) |
+            (&PatternElem::Shr(_), &PatternElem::Shr(_)) |
             (&PatternElem::Square, &PatternElem::SquareRoot) |
             (&PatternElem::SquareRoot, &PatternElem::SquareRoot) => true,
+            (&PatternElem::Recurrence { order: o1, .. }, &PatternElem::Recurrence { order: o2, .. }) => o1 == o2,
             _ => false
         }
     }
+
+    /// Returns whether `next` validly follows `history` under this element's rule. Every
+    /// variant but `Recurrence` only cares about the most recent entry of `history`.
+    pub fn check_history(&self, history: &[i32], next: i32) -> bool {
+        match *self {
+            PatternElem::Recurrence { order, ref coeffs, constant } => {
+                if history.len() < order {
+                    return false;
+                }
+
+                let last = history.len();
+                let predicted = coeffs.iter().enumerate().fold(constant, |acc, (i, c)| {
+                    acc + c * history[last - 1 - i]
+                });
+
+                predicted == next
+            }
+            _ => match history.last() {
+                Some(&x) => self.check_step(x, next),
+                None => false,
+            }
+        }
+    }
+
+    /// The single-step check shared by every element whose rule depends only on the
+    /// immediately preceding term.
+    fn check_step(&self, x: i32, y: i32) -> bool {
+        match *self {
+            PatternElem::Const(i) => y == i,
+            PatternElem::Plus(i) => y == x + i,
+            PatternElem::Mult(i) => y == x * i,
+            PatternElem::Div(i) => i != 0 && x == y * i,
+            PatternElem::Mod(i) => i != 0 && y == x % i,
+            PatternElem::Gcd(n) => y == gcd_i32(x, n),
+            PatternElem::Lcm(n) => y == lcm_i32(x, n),
+            PatternElem::Shl(n) => n < 32 && y == x << n,
+            PatternElem::Shr(n) => n < 32 && y == x >> n,
+            PatternElem::Square => y == x * x,
+            PatternElem::Cube => y == x * x * x,
+            PatternElem::SquareRoot => y >= 0 && x == y * y,
+            PatternElem::CubeRoot => x == y * y * y,
+            PatternElem::NextPrime => next_prime(x) == Some(y),
+            PatternElem::PrevPrime => prev_prime(x) == Some(y),
+            PatternElem::Custom(ref elem) => elem.check(x, y),
+            // Meta-patterns are matched by the solver's repeating-pattern logic, not by a
+            // single-step rule, so they never satisfy a history-aware check directly.
+            PatternElem::Meta(_) => false,
+            PatternElem::Recurrence { .. } => false,
+        }
+    }
+}
+
+/// Witness bases for which Miller-Rabin is a deterministic primality test over all of `u64`.
+const MILLER_RABIN_WITNESSES: [u64; 12] = [2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37];
+
+/// Returns `true` if `n` is prime, via a deterministic Miller-Rabin test against the witness
+/// bases in `MILLER_RABIN_WITNESSES`, which are exact for every `n` up to `u64::max_value()`.
+pub fn is_prime(n: u64) -> bool {
+    if n < 2 {
+        return false;
+    }
+
+    for &p in MILLER_RABIN_WITNESSES.iter() {
+        if n == p {
+            return true;
+        }
+
+        if n % p == 0 {
+            return false;
+        }
+    }
+
+    let mut d = n - 1;
+    let mut s = 0;
+
+    while d % 2 == 0 {
+        d /= 2;
+        s += 1;
+    }
+
+    'witnesses: for &a in MILLER_RABIN_WITNESSES.iter() {
+        let mut x = mod_pow(a, d, n);
+
+        if x == 1 || x == n - 1 {
+            continue;
+        }
+
+        for _ in 0..s - 1 {
+            x = mod_mul(x, x, n);
+
+            if x == n - 1 {
+                continue 'witnesses;
+            }
+        }
+
+        return false;
+    }
+
+    true
+}
+
+/// Computes `base ^ exp mod modulus`, using `u128` intermediates to avoid overflow.
+fn mod_pow(base: u64, mut exp: u64, modulus: u64) -> u64 {
+    let mut result = 1u128;
+    let mut base = (base % modulus) as u128;
+    let modulus = modulus as u128;
+
+    while exp > 0 {
+        if exp % 2 == 1 {
+            result = result * base % modulus;
+        }
+
+        exp /= 2;
+        base = base * base % modulus;
+    }
+
+    result as u64
+}
+
+/// Computes `a * b mod modulus`, using a `u128` intermediate to avoid overflow.
+fn mod_mul(a: u64, b: u64, modulus: u64) -> u64 {
+    ((a as u128) * (b as u128) % (modulus as u128)) as u64
+}
+
+/// Returns the smallest prime strictly greater than `x`, or `None` if there isn't one
+/// representable as an `i32`.
+pub fn next_prime(x: i32) -> Option<i32> {
+    let mut candidate = max(x as i64 + 1, 2);
+
+    while candidate <= i32::max_value() as i64 {
+        if is_prime(candidate as u64) {
+            return Some(candidate as i32);
+        }
+
+        candidate += 1;
+    }
+
+    None
+}
+
+/// Returns the largest prime strictly less than `x`, or `None` if `x <= 2`.
+pub fn prev_prime(x: i32) -> Option<i32> {
+    let mut candidate = x as i64 - 1;
+
+    while candidate >= 2 {
+        if is_prime(candidate as u64) {
+            return Some(candidate as i32);
+        }
+
+        candidate -= 1;
+    }
+
+    None
+}
+
+/// Saturates `n` to `i32::max_value()` if it doesn't fit, rather than wrapping.
+fn saturating_i64_to_i32(n: i64) -> i32 {
+    if n > i32::max_value() as i64 {
+        i32::max_value()
+    } else {
+        n as i32
+    }
+}
+
+/// The greatest common divisor of `a` and `b`, always non-negative. Widens to `i64` before
+/// taking the absolute value so `i32::min_value()` (whose magnitude doesn't fit in an `i32`)
+/// doesn't overflow; the result saturates at `i32::max_value()` in that one case instead of
+/// wrapping negative.
+fn gcd_i32(a: i32, b: i32) -> i32 {
+    let (mut a, mut b) = (i64::from(a).abs(), i64::from(b).abs());
+
+    while b != 0 {
+        let t = b;
+        b = a % b;
+        a = t;
+    }
+
+    saturating_i64_to_i32(a)
+}
+
+/// The least common multiple of `a` and `b`, always non-negative. Divides by the gcd before
+/// multiplying by `b` to avoid overflowing before the reduction can happen; widens to `i64` so
+/// that division and multiplication themselves can't overflow.
+fn lcm_i32(a: i32, b: i32) -> i32 {
+    let g = gcd_i32(a, b);
+
+    if g == 0 {
+        0
+    } else {
+        saturating_i64_to_i32(((i64::from(a) / i64::from(g)) * i64::from(b)).abs())
+    }
+}
+
+/// An exact fraction, used so the linear system `find_recurrence` solves doesn't accumulate
+/// floating-point error.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Frac {
+    num: i64,
+    den: i64,
+}
+
+impl Frac {
+    fn new(num: i64, den: i64) -> Self {
+        let (num, den) = if den < 0 { (-num, -den) } else { (num, den) };
+        let g = gcd(num.abs(), den);
+
+        if g == 0 {
+            Frac { num: 0, den: 1 }
+        } else {
+            Frac { num: num / g, den: den / g }
+        }
+    }
+
+    fn from_int(n: i64) -> Self {
+        Frac::new(n, 1)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.num == 0
+    }
+
+    fn sub(self, other: Self) -> Self {
+        Frac::new(self.num * other.den - other.num * self.den, self.den * other.den)
+    }
+
+    fn mul(self, other: Self) -> Self {
+        Frac::new(self.num * other.num, self.den * other.den)
+    }
+
+    fn div(self, other: Self) -> Self {
+        Frac::new(self.num * other.den, self.den * other.num)
+    }
+
+    fn to_i32(&self) -> Option<i32> {
+        if self.den == 1 && self.num >= i32::min_value() as i64 && self.num <= i32::max_value() as i64 {
+            Some(self.num as i32)
+        } else {
+            None
+        }
+    }
+}
+
+fn gcd(a: i64, b: i64) -> i64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+/// Solves `m * x = b` via Gaussian elimination with exact fraction arithmetic, returning
+/// `None` if `m` is singular.
+fn solve_linear_system(mut m: Vec<Vec<Frac>>, mut b: Vec<Frac>) -> Option<Vec<Frac>> {
+    let n = b.len();
+
+    for col in 0..n {
+        let pivot = match (col..n).find(|&r| !m[r][col].is_zero()) {
+            Some(r) => r,
+            None => return None,
+        };
+
+        m.swap(col, pivot);
+        b.swap(col, pivot);
+
+        let pivot_val = m[col][col];
+
+        for c in col..n {
+            m[col][c] = m[col][c].div(pivot_val);
+        }
+
+        b[col] = b[col].div(pivot_val);
+
+        for row in 0..n {
+            if row == col || m[row][col].is_zero() {
+                continue;
+            }
+
+            let factor = m[row][col];
+
+            for c in col..n {
+                m[row][c] = m[row][c].sub(factor.mul(m[col][c]));
+            }
+
+            b[row] = b[row].sub(factor.mul(b[col]));
+        }
+    }
+
+    Some(b)
+}
+
+/// Looks for the lowest-order constant-coefficient linear recurrence that reproduces `seq`,
+/// i.e. `a(n) = coeffs[0]*a(n-1) + coeffs[1]*a(n-2) + ... + coeffs[order-1]*a(n-order) +
+/// constant`.
+///
+/// For each candidate order `k` from `1` up to `seq.len() / 2`, this builds the `k+1` windows
+/// needed to pin down `k` coefficients plus a constant, solves the resulting linear system
+/// with exact fractions, and rejects the candidate unless every coefficient is an integer and
+/// the recovered recurrence reproduces every remaining term in `seq`. An order `k` is skipped
+/// when `seq` has fewer than `2k+1` terms (too few for its `k+1` windows), so for a `seq` of
+/// even length the order `seq.len() / 2` is never tried.
+pub fn find_recurrence(seq: &[i32]) -> Option<PatternElem> {
+    let max_order = seq.len() / 2;
+
+    for order in 1..(max_order + 1) {
+        if seq.len() < 2 * order + 1 {
+            continue;
+        }
+
+        let mut rows = Vec::with_capacity(order + 1);
+        let mut targets = Vec::with_capacity(order + 1);
+
+        for start in 0..(order + 1) {
+            let mut row: Vec<Frac> = seq[start..start + order]
+                .iter()
+                .rev()
+                .map(|&v| Frac::from_int(v as i64))
+                .collect();
+            row.push(Frac::from_int(1));
+            rows.push(row);
+            targets.push(Frac::from_int(seq[start + order] as i64));
+        }
+
+        let solution = match solve_linear_system(rows, targets) {
+            Some(s) => s,
+            None => continue,
+        };
+
+        let mut coeffs = Vec::with_capacity(order);
+        let mut all_integers = true;
+
+        for frac in &solution[..order] {
+            match frac.to_i32() {
+                Some(c) => coeffs.push(c),
+                None => {
+                    all_integers = false;
+                    break;
+                }
+            }
+        }
+
+        if !all_integers {
+            continue;
+        }
+
+        let constant = match solution[order].to_i32() {
+            Some(c) => c,
+            None => continue,
+        };
+
+        let candidate = PatternElem::Recurrence { order: order, coeffs: coeffs, constant: constant };
+
+        let matches_rest = (order..seq.len()).all(|i| candidate.check_history(&seq[..i], seq[i]));
+
+        if matches_rest {
+            return Some(candidate);
+        }
+    }
+
+    None
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
@@ -64,6 +476,29 @@ impl CustomPatternElem {
     }
 }
 
+/// Serializes as only the `repr` string, since `check` is a function pointer and can't be
+/// serialized; see [`custom_registry`](custom_registry) for how it's recovered.
+#[cfg(feature = "serde")]
+impl Serialize for CustomPatternElem {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.repr)
+    }
+}
+
+/// Looks `repr` up in the [`custom_registry`](custom_registry) to recover `check`, erroring
+/// if no function was registered under it.
+#[cfg(feature = "serde")]
+impl<'de> Deserialize<'de> for CustomPatternElem {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let repr = String::deserialize(deserializer)?;
+
+        match CUSTOM_REGISTRY.lock().unwrap().get(&repr) {
+            Some(&check) => Ok(CustomPatternElem { check: check, repr: repr }),
+            None => Err(D::Error::custom(format!("no custom pattern registered for `{}`", repr))),
+        }
+    }
+}
+
 impl Display for PatternElem {
     fn fmt(&self, mut fmt: &mut Formatter) -> Result<(), Error> {
         match *self {
@@ -73,10 +508,33 @@ impl Display for PatternElem {
             PatternElem::Mult(i) => write!(fmt, "*{}", i),
             PatternElem::Div(i) => write!(fmt, "/{}", i),
             PatternElem::Mod(i) => write!(fmt, "%{}", i),
+            PatternElem::Gcd(i) => write!(fmt, "gcd {}", i),
+            PatternElem::Lcm(i) => write!(fmt, "lcm {}", i),
+            PatternElem::Shl(i) => write!(fmt, "<<{}", i),
+            PatternElem::Shr(i) => write!(fmt, ">>{}", i),
             PatternElem::Square => write!(fmt, "^2"),
             PatternElem::Cube => write!(fmt, "^3"),
             PatternElem::SquareRoot => write!(fmt, "root 2"),
             PatternElem::CubeRoot => write!(fmt, "root 3"),
+            PatternElem::NextPrime => write!(fmt, "next prime"),
+            PatternElem::PrevPrime => write!(fmt, "prev prime"),
+            PatternElem::Recurrence { ref coeffs, constant, .. } => {
+                try!(write!(fmt, "rec["));
+
+                for (i, c) in coeffs.iter().enumerate() {
+                    if i != 0 {
+                        try!(write!(fmt, ","));
+                    }
+
+                    try!(write!(fmt, "{}", c));
+                }
+
+                if constant < 0 {
+                    write!(fmt, "]-{}", constant.abs())
+                } else {
+                    write!(fmt, "]+{}", constant)
+                }
+            }
             PatternElem::Custom(CustomPatternElem { ref repr, .. }) => write!(fmt, "{}", repr),
             PatternElem::Meta(ref pat) => write!(fmt, "[{}...]", pat),
         }
@@ -85,6 +543,7 @@ impl Display for PatternElem {
 
 /// A sequence of operations defining a pattern.
 #[derive(Clone, Debug, Hash, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
 pub struct Pattern(Vec<PatternElem>);
 
 impl IntoIterator for Pattern {
@@ -231,6 +690,165 @@ mod tests {
         assert_eq!("root 3", format!("{}", CubeRoot));
     }
 
+    #[test]
+    fn fmt_pat_elem_next_prime() {
+        assert_eq!("next prime", format!("{}", NextPrime));
+    }
+
+    #[test]
+    fn fmt_pat_elem_prev_prime() {
+        assert_eq!("prev prime", format!("{}", PrevPrime));
+    }
+
+    #[test]
+    fn is_prime_small_values() {
+        assert!(!super::is_prime(0));
+        assert!(!super::is_prime(1));
+        assert!(super::is_prime(2));
+        assert!(super::is_prime(3));
+        assert!(!super::is_prime(4));
+        assert!(!super::is_prime(1000));
+    }
+
+    #[test]
+    fn is_prime_large_values() {
+        assert!(super::is_prime(2_147_483_647));
+        assert!(!super::is_prime(2_147_483_649));
+        assert!(super::is_prime(18_446_744_073_709_551_557));
+    }
+
+    #[test]
+    fn next_prime_walks_forward() {
+        assert_eq!(Some(3), super::next_prime(2));
+        assert_eq!(Some(5), super::next_prime(3));
+        assert_eq!(Some(11), super::next_prime(7));
+    }
+
+    #[test]
+    fn next_prime_clamps_negative_x() {
+        assert_eq!(Some(2), super::next_prime(-100));
+        assert_eq!(Some(2), super::next_prime(i32::min_value()));
+    }
+
+    #[test]
+    fn prev_prime_walks_backward() {
+        assert_eq!(Some(2), super::prev_prime(3));
+        assert_eq!(Some(7), super::prev_prime(11));
+        assert_eq!(None, super::prev_prime(2));
+    }
+
+    #[test]
+    fn check_history_next_and_prev_prime() {
+        assert!(NextPrime.check_history(&[10], 11));
+        assert!(!NextPrime.check_history(&[10], 12));
+        assert!(PrevPrime.check_history(&[10], 7));
+        assert!(!PrevPrime.check_history(&[10], 5));
+    }
+
+    #[test]
+    fn fmt_pat_elem_gcd() {
+        assert_eq!("gcd 6", format!("{}", Gcd(6)));
+    }
+
+    #[test]
+    fn fmt_pat_elem_lcm() {
+        assert_eq!("lcm 4", format!("{}", Lcm(4)));
+    }
+
+    #[test]
+    fn fmt_pat_elem_shl() {
+        assert_eq!("<<2", format!("{}", Shl(2)));
+    }
+
+    #[test]
+    fn fmt_pat_elem_shr() {
+        assert_eq!(">>3", format!("{}", Shr(3)));
+    }
+
+    #[test]
+    fn check_history_gcd_and_lcm() {
+        assert!(Gcd(8).check_history(&[12], 4));
+        assert!(!Gcd(8).check_history(&[12], 6));
+        assert!(Lcm(4).check_history(&[6], 12));
+        assert!(!Lcm(4).check_history(&[6], 4));
+    }
+
+    #[test]
+    fn check_history_lcm_negative_operand_is_non_negative() {
+        assert!(Lcm(6).check_history(&[-4], 12));
+        assert!(!Lcm(6).check_history(&[-4], -12));
+    }
+
+    #[test]
+    fn check_history_gcd_and_lcm_do_not_panic_on_i32_min() {
+        assert!(!Gcd(i32::min_value()).check_history(&[i32::min_value()], 0));
+        assert!(!Lcm(i32::min_value()).check_history(&[i32::min_value()], 0));
+    }
+
+    #[test]
+    fn check_history_shl_and_shr() {
+        assert!(Shl(2).check_history(&[1], 4));
+        assert!(Shr(1).check_history(&[8], 4));
+        assert!(!Shr(1).check_history(&[8], 3));
+    }
+
+    #[test]
+    fn check_history_shl_and_shr_reject_out_of_range_operand() {
+        assert!(!Shl(32).check_history(&[1], 0));
+        assert!(!Shr(32).check_history(&[1], 0));
+    }
+
+    #[test]
+    fn same_operator_type_gcd_lcm_shifts() {
+        assert!(Gcd(6).same_operator_type(&Gcd(2)));
+        assert!(!Gcd(6).same_operator_type(&Lcm(6)));
+        assert!(Shl(1).same_operator_type(&Shl(4)));
+        assert!(!Shl(1).same_operator_type(&Shr(1)));
+    }
+
+    #[test]
+    fn fmt_pat_elem_recurrence() {
+        let fib = Recurrence { order: 2, coeffs: vec![1, 1], constant: 0 };
+        assert_eq!("rec[1,1]+0", format!("{}", fib));
+
+        let with_offset = Recurrence { order: 1, coeffs: vec![2], constant: -3 };
+        assert_eq!("rec[2]-3", format!("{}", with_offset));
+    }
+
+    #[test]
+    fn check_history_recurrence() {
+        let fib = Recurrence { order: 2, coeffs: vec![1, 1], constant: 0 };
+        assert!(fib.check_history(&[1, 1], 2));
+        assert!(fib.check_history(&[1, 1, 2, 3], 5));
+        assert!(!fib.check_history(&[1, 1, 2, 3], 6));
+        assert!(!fib.check_history(&[1], 2));
+    }
+
+    #[test]
+    fn check_history_single_step_elements() {
+        assert!(Plus(3).check_history(&[4], 7));
+        assert!(!Plus(3).check_history(&[4], 8));
+        assert!(Square.check_history(&[5], 25));
+    }
+
+    #[test]
+    fn find_recurrence_fibonacci() {
+        let fib = super::find_recurrence(&[1, 1, 2, 3, 5, 8, 13]);
+        assert_eq!(Some(Recurrence { order: 2, coeffs: vec![1, 1], constant: 0 }), fib);
+    }
+
+    #[test]
+    fn find_recurrence_with_constant() {
+        // a(n) = 2*a(n-1) + 1
+        let seq = super::find_recurrence(&[1, 3, 7, 15, 31, 63]);
+        assert_eq!(Some(Recurrence { order: 1, coeffs: vec![2], constant: 1 }), seq);
+    }
+
+    #[test]
+    fn find_recurrence_rejects_non_recurrent_sequence() {
+        assert_eq!(None, super::find_recurrence(&[2, 3, 5, 7, 11, 13]));
+    }
+
     #[test]
     fn fmt_pat() {
         assert_eq!("", format!("{}", Pattern::empty()));
@@ -239,4 +857,51 @@ mod tests {
         assert_eq!("+4, %-6, -12, *42, /3, =9", format!("{}", pat![Plus(4), Mod(-6), Plus(-12), Mult(42), Div(3), Const(9)]));
         assert_eq!("^2, root 2, ^3, root 3", format!("{}", pat![Square, SquareRoot, Cube, CubeRoot]));
     }
+
+    #[cfg(feature = "serde")]
+    mod serde {
+        extern crate serde_json;
+
+        use super::super::{CustomPatternElem, custom_registry};
+        use super::super::PatternElem::*;
+        use super::Pattern;
+
+        #[test]
+        fn serialize_pat_elems() {
+            assert_eq!("{\"Plus\":3}", serde_json::to_string(&Plus(3)).unwrap());
+            assert_eq!("\"Square\"", serde_json::to_string(&Square).unwrap());
+            assert_eq!(
+                "{\"Meta\":[{\"Plus\":1}]}",
+                serde_json::to_string(&Meta(pat![Plus(1)])).unwrap()
+            );
+        }
+
+        #[test]
+        fn roundtrip_pattern() {
+            let pat = pat![Plus(3), Mult(2), Square];
+            let serialized = serde_json::to_string(&pat).unwrap();
+
+            assert_eq!(pat, serde_json::from_str(&serialized).unwrap());
+        }
+
+        #[test]
+        fn roundtrip_custom_after_registering() {
+            fn is_double(x: i32, y: i32) -> bool { y == x * 2 }
+            custom_registry::register("double", is_double);
+
+            let elem = Custom(CustomPatternElem::new(is_double, "double"));
+            let serialized = serde_json::to_string(&elem).unwrap();
+
+            assert_eq!("{\"Custom\":\"double\"}", serialized);
+            assert_eq!(elem, serde_json::from_str(&serialized).unwrap());
+        }
+
+        #[test]
+        fn deserialize_unregistered_custom_errors() {
+            let result: Result<super::super::PatternElem, _> =
+                serde_json::from_str("{\"Custom\":\"never-registered\"}");
+
+            assert!(result.is_err());
+        }
+    }
 }